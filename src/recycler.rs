@@ -1,4 +1,8 @@
-use std::sync::Mutex;
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex, Weak};
+use std::thread;
 
 /// A function that leaves the given type in the same state as Default,
 /// but starts with an existing type instead of allocating a new one.
@@ -6,29 +10,88 @@ pub trait Reset {
     fn reset(&mut self);
 }
 
+/// Hashes the current thread's id down to a shard index, so that threads
+/// tend to hit the same shard call after call without any shared state.
+fn shard_index(num_shards: usize) -> usize {
+    let mut hasher = DefaultHasher::new();
+    thread::current().id().hash(&mut hasher);
+    (hasher.finish() as usize) % num_shards
+}
+
+/// Hit/miss/outstanding counters for a `Recycler`. Updated with relaxed
+/// atomics, since the counts are purely observational and never used to
+/// synchronize access to the landfill.
+#[derive(Default)]
+struct Stats {
+    hits: AtomicUsize,
+    misses: AtomicUsize,
+    outstanding: AtomicUsize,
+}
+
+/// A snapshot of a `Recycler`'s usage counters, returned by `Recycler::stats()`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct RecyclerStats {
+    /// Number of `allocate()` calls served from the landfill.
+    pub hits: usize,
+    /// Number of `allocate()` calls that had to build a fresh value.
+    pub misses: usize,
+    /// Number of `Recyclable`s currently allocated and not yet dropped.
+    pub outstanding: usize,
+}
+
+/// Pushes `val` onto a shard if the whole-pool `pooled` count is still under
+/// `max_capacity`, reserving the slot first so the cap is exact. Shared by
+/// `Recyclable::drop` and `OwnedRecyclable::drop` so the cap logic only
+/// lives in one place.
+///
+/// `pooled` is incremented as a reservation before touching a shard; if
+/// that overshoots `max_capacity`, the reservation is released and the
+/// value is dropped instead of pushed. Folding the check into the same
+/// `fetch_add` that claims the slot keeps the cap exact under concurrent
+/// drops, rather than merely approximate.
+fn try_recycle<T>(val: T, shards: &[Mutex<Vec<T>>], max_capacity: Option<usize>, pooled: &AtomicUsize) {
+    let reserved = pooled.fetch_add(1, Ordering::Relaxed);
+    if max_capacity.is_some_and(|max| reserved >= max) {
+        pooled.fetch_sub(1, Ordering::Relaxed);
+        return;
+    }
+    // Return the value to this thread's shard, but don't block on a
+    // contended shard -- just give back the reservation and drop the value
+    // instead.
+    if let Ok(mut shard) = shards[shard_index(shards.len())].try_lock() {
+        shard.push(val);
+    } else {
+        pooled.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
 /// An value that's returned to its heap once dropped.
-pub struct Recyclable<'a, T: 'a + Default + Reset> {
+pub struct Recyclable<'a, T: 'a + Reset> {
     val: Option<T>,
-    landfill: &'a Mutex<Vec<T>>,
+    shards: &'a [Mutex<Vec<T>>],
+    max_capacity: Option<usize>,
+    pooled: &'a AtomicUsize,
+    stats: &'a Stats,
 }
 
-impl<'a, T: Default + Reset> AsRef<T> for Recyclable<'a, T> {
+impl<'a, T: Reset> AsRef<T> for Recyclable<'a, T> {
     fn as_ref(&self) -> &T {
         self.val.as_ref().unwrap()
     }
 }
 
-impl<'a, T: Default + Reset> AsMut<T> for Recyclable<'a, T> {
+impl<'a, T: Reset> AsMut<T> for Recyclable<'a, T> {
     fn as_mut(&mut self) -> &mut T {
         self.val.as_mut().unwrap()
     }
 }
 
-impl<'a, T: Default + Reset> Drop for Recyclable<'a, T> {
+impl<'a, T: Reset> Drop for Recyclable<'a, T> {
     fn drop(&mut self) {
         if let Some(val) = self.val.take() {
-            self.landfill.lock().unwrap().push(val);
+            try_recycle(val, self.shards, self.max_capacity, self.pooled);
         }
+        self.stats.outstanding.fetch_sub(1, Ordering::Relaxed);
     }
 }
 
@@ -37,32 +100,247 @@ impl<'a, T: Default + Reset> Drop for Recyclable<'a, T> {
 /// are dropped, they're returned to the recycler. The next time
 /// `allocate()` is called, the value will be pulled from the
 /// recycler instead being allocated from memory.
-pub struct Recycler<T: Default + Reset> {
-    landfill: Mutex<Vec<T>>,
+///
+/// The landfill is split into shards, one per thread of expected
+/// parallelism, so that concurrent `allocate()`/`Drop` calls from
+/// different threads rarely contend on the same `Mutex`. Values are
+/// interchangeable between shards, so there's no affinity tracking --
+/// only contention reduction.
+pub struct Recycler<T: Reset> {
+    shards: Box<[Mutex<Vec<T>>]>,
+    factory: Mutex<Box<dyn Fn() -> T + Send>>,
+    max_capacity: Option<usize>,
+    pooled: AtomicUsize,
+    stats: Stats,
 }
 
-impl<T: Default + Reset> Default for Recycler<T> {
+impl<T: Default + Reset + 'static> Default for Recycler<T> {
     fn default() -> Self {
+        Self::with_factory(T::default)
+    }
+}
+
+impl<T: Reset> Recycler<T> {
+    /// Creates a `Recycler` that builds fresh values with `factory` instead
+    /// of requiring `T: Default`, for types that need constructor arguments.
+    pub fn with_factory<F: Fn() -> T + Send + 'static>(factory: F) -> Self {
+        let num_shards = thread::available_parallelism()
+            .map(|n| n.get())
+            .unwrap_or(1)
+            .next_power_of_two();
+        let shards = (0..num_shards)
+            .map(|_| Mutex::new(vec![]))
+            .collect::<Vec<_>>()
+            .into_boxed_slice();
         Recycler {
-            landfill: Mutex::new(vec![]),
+            shards,
+            factory: Mutex::new(Box::new(factory)),
+            max_capacity: None,
+            pooled: AtomicUsize::new(0),
+            stats: Stats::default(),
         }
     }
-}
 
-impl<T: Default + Reset> Recycler<T> {
-    pub fn allocate(&self) -> Recyclable<T> {
-        let val = self
-            .landfill
-            .lock()
-            .unwrap()
-            .pop()
-            .map(|mut val| {
+    pub fn allocate(&self) -> Recyclable<'_, T> {
+        let num_shards = self.shards.len();
+        let start = shard_index(num_shards);
+        let val = (0..num_shards)
+            .filter_map(|offset| self.shards[(start + offset) % num_shards].try_lock().ok())
+            .find_map(|mut shard| shard.pop());
+        let val = match val {
+            Some(mut val) => {
                 val.reset();
+                self.pooled.fetch_sub(1, Ordering::Relaxed);
+                self.stats.hits.fetch_add(1, Ordering::Relaxed);
                 val
-            }).unwrap_or_default();
+            }
+            None => {
+                self.stats.misses.fetch_add(1, Ordering::Relaxed);
+                (self.factory.lock().unwrap())()
+            }
+        };
+        self.stats.outstanding.fetch_add(1, Ordering::Relaxed);
         Recyclable {
             val: Some(val),
-            landfill: &self.landfill,
+            shards: &self.shards,
+            max_capacity: self.max_capacity,
+            pooled: &self.pooled,
+            stats: &self.stats,
+        }
+    }
+
+    /// Seeds the landfill with `n` values built via this recycler's factory,
+    /// so that the first `n` calls to `allocate()` hit the pool. Unlike
+    /// `with_capacity()`, this works with any `with_factory()`-built
+    /// recycler, not just `T: Default` ones.
+    ///
+    /// Respects `max_capacity`: if `n` would push the whole-pool total past
+    /// the cap, only enough values to reach it are added.
+    pub fn pre_warm(&self, n: usize) {
+        let n = match self.max_capacity {
+            Some(max) => n.min(max.saturating_sub(self.pooled.load(Ordering::Relaxed))),
+            None => n,
+        };
+        let num_shards = self.shards.len();
+        for i in 0..n {
+            self.shards[i % num_shards].lock().unwrap().push((self.factory.lock().unwrap())());
+        }
+        self.pooled.fetch_add(n, Ordering::Relaxed);
+    }
+
+    /// Returns a snapshot of this recycler's hit/miss/outstanding counters,
+    /// useful for deciding whether to raise `max_capacity` or pre-warm more
+    /// aggressively.
+    pub fn stats(&self) -> RecyclerStats {
+        RecyclerStats {
+            hits: self.stats.hits.load(Ordering::Relaxed),
+            misses: self.stats.misses.load(Ordering::Relaxed),
+            outstanding: self.stats.outstanding.load(Ordering::Relaxed),
+        }
+    }
+}
+
+impl<T: Default + Reset + 'static> Recycler<T> {
+    /// Creates a `Recycler` whose landfill, summed across every shard, never
+    /// holds more than `max_capacity` values. Once the pool is full, dropped
+    /// values are freed instead of retained, trading reuse rate for a
+    /// bounded memory footprint.
+    pub fn with_max_capacity(max_capacity: usize) -> Self {
+        let mut recycler = Self::with_factory(T::default);
+        recycler.max_capacity = Some(max_capacity);
+        recycler
+    }
+
+    /// Creates a `Recycler` pre-warmed with `n` values, so that the first `n`
+    /// calls to `allocate()` are served from the landfill instead of paying
+    /// allocation cost on the hot path.
+    pub fn with_capacity(n: usize) -> Self {
+        let recycler = Self::with_factory(T::default);
+        recycler.pre_warm(n);
+        recycler
+    }
+}
+
+impl<T: Reset> Recycler<T> {
+    /// Converts this `Recycler` into a `SharedRecycler`, whose allocated
+    /// values hold an owned handle to the pool instead of borrowing it.
+    /// Unlike `Recyclable<'a, T>`, the resulting `OwnedRecyclable<T>` is
+    /// `'static` -- it can be stored in long-lived structs, moved into a
+    /// detached `tokio::spawn` task, or sent through a channel that outlives
+    /// the pool's stack frame.
+    ///
+    /// `with_factory()` only requires `F: Send`: the factory sits behind a
+    /// `Mutex`, which keeps `Recycler` shareable across threads without
+    /// tightening the bound to `Send + Sync` for every caller. That same
+    /// `Mutex` moves into the `Arc` here and is shared by every clone.
+    pub fn into_shared(self) -> SharedRecycler<T> {
+        SharedRecycler {
+            shards: Arc::from(self.shards),
+            factory: Arc::new(self.factory),
+            max_capacity: self.max_capacity,
+            pooled: Arc::new(self.pooled),
+            stats: Arc::new(self.stats),
+        }
+    }
+}
+
+/// An owned, `'static` counterpart to `Recyclable`. Returned by
+/// `SharedRecycler::allocate()`, it keeps a weak handle to the shared
+/// landfill instead of borrowing it, upgrading that handle on drop to
+/// return the value if the pool is still alive, or freeing it otherwise.
+pub struct OwnedRecyclable<T: Reset> {
+    val: Option<T>,
+    shards: Weak<[Mutex<Vec<T>>]>,
+    max_capacity: Option<usize>,
+    pooled: Weak<AtomicUsize>,
+    stats: Weak<Stats>,
+}
+
+impl<T: Reset> AsRef<T> for OwnedRecyclable<T> {
+    fn as_ref(&self) -> &T {
+        self.val.as_ref().unwrap()
+    }
+}
+
+impl<T: Reset> AsMut<T> for OwnedRecyclable<T> {
+    fn as_mut(&mut self) -> &mut T {
+        self.val.as_mut().unwrap()
+    }
+}
+
+impl<T: Reset> Drop for OwnedRecyclable<T> {
+    fn drop(&mut self) {
+        if let Some(val) = self.val.take() {
+            if let (Some(shards), Some(pooled)) = (self.shards.upgrade(), self.pooled.upgrade()) {
+                try_recycle(val, &shards, self.max_capacity, &pooled);
+            }
+        }
+        if let Some(stats) = self.stats.upgrade() {
+            stats.outstanding.fetch_sub(1, Ordering::Relaxed);
+        }
+    }
+}
+
+/// The `Arc`-backed counterpart to `Recycler`, produced by
+/// `Recycler::into_shared()`. Cloning a `SharedRecycler` is cheap -- it
+/// clones the `Arc` handles, not the landfill -- so it can be freely
+/// handed to detached tasks or stored alongside the values it allocates.
+pub struct SharedRecycler<T: Reset> {
+    shards: Arc<[Mutex<Vec<T>>]>,
+    factory: Arc<Mutex<Box<dyn Fn() -> T + Send>>>,
+    max_capacity: Option<usize>,
+    pooled: Arc<AtomicUsize>,
+    stats: Arc<Stats>,
+}
+
+impl<T: Reset> Clone for SharedRecycler<T> {
+    fn clone(&self) -> Self {
+        SharedRecycler {
+            shards: Arc::clone(&self.shards),
+            factory: Arc::clone(&self.factory),
+            max_capacity: self.max_capacity,
+            pooled: Arc::clone(&self.pooled),
+            stats: Arc::clone(&self.stats),
+        }
+    }
+}
+
+impl<T: Reset> SharedRecycler<T> {
+    pub fn allocate(&self) -> OwnedRecyclable<T> {
+        let num_shards = self.shards.len();
+        let start = shard_index(num_shards);
+        let val = (0..num_shards)
+            .filter_map(|offset| self.shards[(start + offset) % num_shards].try_lock().ok())
+            .find_map(|mut shard| shard.pop());
+        let val = match val {
+            Some(mut val) => {
+                val.reset();
+                self.pooled.fetch_sub(1, Ordering::Relaxed);
+                self.stats.hits.fetch_add(1, Ordering::Relaxed);
+                val
+            }
+            None => {
+                self.stats.misses.fetch_add(1, Ordering::Relaxed);
+                (self.factory.lock().unwrap())()
+            }
+        };
+        self.stats.outstanding.fetch_add(1, Ordering::Relaxed);
+        OwnedRecyclable {
+            val: Some(val),
+            shards: Arc::downgrade(&self.shards),
+            max_capacity: self.max_capacity,
+            pooled: Arc::downgrade(&self.pooled),
+            stats: Arc::downgrade(&self.stats),
+        }
+    }
+
+    /// Returns a snapshot of this recycler's hit/miss/outstanding counters.
+    /// See `Recycler::stats()`.
+    pub fn stats(&self) -> RecyclerStats {
+        RecyclerStats {
+            hits: self.stats.hits.load(Ordering::Relaxed),
+            misses: self.stats.misses.load(Ordering::Relaxed),
+            outstanding: self.stats.outstanding.load(Ordering::Relaxed),
         }
     }
 }
@@ -85,6 +363,10 @@ mod tests {
         }
     }
 
+    fn total_len<T: Reset>(recycler: &Recycler<T>) -> usize {
+        recycler.shards.iter().map(|shard| shard.lock().unwrap().len()).sum()
+    }
+
     #[test]
     fn test_allocate() {
         let recycler: Recycler<Foo> = Recycler::default();
@@ -99,11 +381,11 @@ mod tests {
             let mut foo = recycler.allocate();
             foo.as_mut().x = 1;
         }
-        assert_eq!(recycler.landfill.lock().unwrap().len(), 1);
+        assert_eq!(total_len(&recycler), 1);
 
         let foo = recycler.allocate();
         assert_eq!(foo.as_ref().x, 0);
-        assert_eq!(recycler.landfill.lock().unwrap().len(), 0);
+        assert_eq!(total_len(&recycler), 0);
     }
 
     #[test]
@@ -114,14 +396,14 @@ mod tests {
             let mut foo = recycler.allocate();
             foo.as_mut().x = 1;
             sender.send(foo).unwrap();
-            assert_eq!(recycler.landfill.lock().unwrap().len(), 0);
+            assert_eq!(total_len(&recycler), 0);
         }
         {
             let foo = receiver.recv().unwrap();
             assert_eq!(foo.as_ref().x, 1);
-            assert_eq!(recycler.landfill.lock().unwrap().len(), 0);
+            assert_eq!(total_len(&recycler), 0);
         }
-        assert_eq!(recycler.landfill.lock().unwrap().len(), 1);
+        assert_eq!(total_len(&recycler), 1);
     }
 
     #[test]
@@ -136,7 +418,125 @@ mod tests {
             });
         });
 
-        assert_eq!(recycler.landfill.lock().unwrap().len(), 1);
+        assert_eq!(total_len(&recycler), 1);
+    }
+
+    #[test]
+    fn test_with_factory() {
+        let recycler: Recycler<Foo> = Recycler::with_factory(|| Foo { x: 42 });
+        assert_eq!(recycler.allocate().as_ref().x, 42);
+    }
+
+    #[test]
+    fn test_max_capacity() {
+        let recycler: Recycler<Foo> = Recycler::with_max_capacity(1);
+
+        let foo1 = recycler.allocate();
+        let foo2 = recycler.allocate();
+        drop(foo1);
+        drop(foo2);
+
+        assert_eq!(total_len(&recycler), 1);
+    }
+
+    #[test]
+    fn test_max_capacity_is_pool_wide() {
+        let recycler: Recycler<Foo> = Recycler::with_max_capacity(2);
+
+        crossbeam::scope(|scope| {
+            for _ in 0..8 {
+                scope.spawn(|| {
+                    drop(recycler.allocate());
+                });
+            }
+        });
+
+        assert!(total_len(&recycler) <= 2);
+    }
+
+    #[test]
+    fn test_with_capacity() {
+        let recycler: Recycler<Foo> = Recycler::with_capacity(3);
+        assert_eq!(total_len(&recycler), 3);
+
+        let _held = recycler.allocate();
+        assert_eq!(total_len(&recycler), 2);
+    }
+
+    #[test]
+    fn test_pre_warm_with_factory() {
+        let recycler: Recycler<Foo> = Recycler::with_factory(|| Foo { x: 42 });
+        recycler.pre_warm(2);
+        assert_eq!(total_len(&recycler), 2);
+
+        let _held = recycler.allocate();
+        assert_eq!(total_len(&recycler), 1);
+    }
+
+    #[test]
+    fn test_pre_warm_respects_max_capacity() {
+        let recycler: Recycler<Foo> = Recycler::with_max_capacity(2);
+        recycler.pre_warm(10);
+        assert_eq!(total_len(&recycler), 2);
+
+        // Allocating frees a slot under the cap, so the dropped value is
+        // taken back: the pool refills to the cap without going past it,
+        // rather than getting stuck accepting nothing forever.
+        let foo = recycler.allocate();
+        drop(foo);
+        assert_eq!(total_len(&recycler), 2);
+    }
+
+    #[test]
+    fn test_stats() {
+        let recycler: Recycler<Foo> = Recycler::default();
+
+        let foo = recycler.allocate();
+        assert_eq!(
+            recycler.stats(),
+            RecyclerStats {
+                hits: 0,
+                misses: 1,
+                outstanding: 1,
+            }
+        );
+
+        drop(foo);
+        let _held = recycler.allocate();
+        assert_eq!(
+            recycler.stats(),
+            RecyclerStats {
+                hits: 1,
+                misses: 1,
+                outstanding: 1,
+            }
+        );
+    }
+
+    fn total_len_shared<T: Reset>(recycler: &SharedRecycler<T>) -> usize {
+        recycler.shards.iter().map(|shard| shard.lock().unwrap().len()).sum()
+    }
+
+    #[test]
+    fn test_into_shared_detached_thread() {
+        let recycler: SharedRecycler<Foo> = Recycler::default().into_shared();
+        let (sender, receiver) = channel();
+        sender.send(recycler.allocate()).unwrap();
+
+        let hdl = thread::spawn(move || {
+            receiver.recv().unwrap();
+        });
+        hdl.join().unwrap();
+
+        assert_eq!(total_len_shared(&recycler), 1);
+    }
+
+    #[test]
+    fn test_owned_recyclable_outlives_pool() {
+        let recycler: SharedRecycler<Foo> = Recycler::default().into_shared();
+        let foo = recycler.allocate();
+        drop(recycler);
+        drop(foo);
     }
 
     struct ThreadNanny<'a> {
@@ -158,6 +558,6 @@ mod tests {
             ThreadNanny { _hdl };
         }
 
-        assert_eq!(recycler.landfill.lock().unwrap().len(), 1);
+        assert_eq!(total_len(&recycler), 1);
     }
 }